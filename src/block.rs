@@ -0,0 +1,396 @@
+//! A bgzip-style block container enabling random-access reads and cheap
+//! appends, unlike [`CompressedFile`](crate::CompressedFile)'s single gzip
+//! stream which must be fully re-deflated on every write.
+//!
+//! The payload is stored as a sequence of independent gzip members, each
+//! covering at most [`BLOCK_SIZE`] uncompressed bytes, followed by an
+//! index mapping uncompressed offset ranges to the compressed byte range of
+//! the block that holds them:
+//!
+//! ```text
+//! [ gzip member 1 ][ gzip member 2 ] ... [ gzip member N ][ index ][ index_len: u64 LE ]
+//! ```
+//!
+//! Every block is an independent gzip member, so `gzip -d` (or any
+//! multi-member-aware decoder) decompresses all of the blocks in order. The
+//! trailing index and its length are not a gzip member: such a decoder
+//! reaches them as trailing data right after the last member.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+/// Maximum size of the uncompressed window a block covers. Blocks are cut
+/// per [`append`](BlockCompressedFile::append) call (and per zero-fill
+/// extension in [`patch_block_file`]), so the last block each of them
+/// writes can be shorter, not only the last block of the file.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// One entry in a [`BlockCompressedFile`]'s trailing index.
+#[derive(Debug, Clone, Copy)]
+struct BlockIndexEntry {
+    uncompressed_offset: u64,
+    uncompressed_len: u64,
+    compressed_offset: u64,
+    compressed_len: u64,
+}
+
+const INDEX_ENTRY_LEN: usize = 8 * 4;
+
+/// The trailing index of a [`BlockCompressedFile`], mapping uncompressed
+/// offset ranges to the compressed block that holds them.
+#[derive(Debug, Default)]
+struct BlockIndex {
+    entries: Vec<BlockIndexEntry>,
+}
+
+impl BlockIndex {
+    /// Byte offset one past the last block's compressed bytes, i.e. where the
+    /// index itself starts.
+    fn body_len(&self) -> u64 {
+        self.entries
+            .last()
+            .map(|e| e.compressed_offset + e.compressed_len)
+            .unwrap_or(0)
+    }
+
+    /// Total uncompressed length covered by the index.
+    fn uncompressed_len(&self) -> u64 {
+        self.entries
+            .last()
+            .map(|e| e.uncompressed_offset + e.uncompressed_len)
+            .unwrap_or(0)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.entries.len() * INDEX_ENTRY_LEN);
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.uncompressed_offset.to_le_bytes());
+            bytes.extend_from_slice(&entry.uncompressed_len.to_le_bytes());
+            bytes.extend_from_slice(&entry.compressed_offset.to_le_bytes());
+            bytes.extend_from_slice(&entry.compressed_len.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, io::Error> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "corrupt block index");
+
+        if bytes.len() < 4 {
+            return Err(invalid());
+        }
+        let count = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        if bytes.len() < 4 + count * INDEX_ENTRY_LEN {
+            return Err(invalid());
+        }
+        let mut entries = Vec::with_capacity(count);
+
+        let mut offset = 4;
+        for _ in 0..count {
+            if bytes.len() < offset + INDEX_ENTRY_LEN {
+                return Err(invalid());
+            }
+            let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+            entries.push(BlockIndexEntry {
+                uncompressed_offset: read_u64(offset),
+                uncompressed_len: read_u64(offset + 8),
+                compressed_offset: read_u64(offset + 16),
+                compressed_len: read_u64(offset + 24),
+            });
+            offset += INDEX_ENTRY_LEN;
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+/// Compresses `chunk` as a standalone gzip member.
+fn compress_block(chunk: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(chunk)?;
+    encoder.finish()
+}
+
+/// Decompresses a single gzip member previously produced by [`compress_block`].
+fn decompress_block(compressed: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut plain = Vec::new();
+    GzDecoder::new(compressed).read_to_end(&mut plain)?;
+    Ok(plain)
+}
+
+/// A file holding its payload as a sequence of independently gzip-compressed
+/// blocks plus a trailing index, enabling random-access reads via
+/// [`read_at`](Self::read_at) and appends via [`append`](Self::append) that
+/// don't re-deflate earlier blocks.
+pub struct BlockCompressedFile {
+    path: PathBuf,
+}
+
+impl BlockCompressedFile {
+    /// Creates a new, empty block-compressed file at the specified path.
+    ///
+    /// If the file already exists, its content will be truncated.
+    pub fn create(path: PathBuf) -> Result<Self, io::Error> {
+        File::create(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Wraps an existing (or not-yet-created) path without touching it.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read_index(&self) -> Result<BlockIndex, io::Error> {
+        if !self.path.exists() || fs::metadata(&self.path)?.len() == 0 {
+            return Ok(BlockIndex::default());
+        }
+
+        let mut file = File::open(&self.path)?;
+        let total_len = file.metadata()?.len();
+        if total_len < 8 {
+            return Ok(BlockIndex::default());
+        }
+
+        file.seek(SeekFrom::End(-8))?;
+        let mut index_len_buf = [0u8; 8];
+        file.read_exact(&mut index_len_buf)?;
+        let index_len = u64::from_le_bytes(index_len_buf);
+
+        file.seek(SeekFrom::End(-8 - index_len as i64))?;
+        let mut index_bytes = vec![0u8; index_len as usize];
+        file.read_exact(&mut index_bytes)?;
+
+        BlockIndex::from_bytes(&index_bytes)
+    }
+
+    /// Reads `len` uncompressed bytes starting at `offset`, decoding only
+    /// the blocks that overlap `[offset, offset + len)`.
+    pub fn read_at(&self, offset: u64, len: u64) -> Result<Vec<u8>, io::Error> {
+        let index = self.read_index()?;
+        let mut file = File::open(&self.path)?;
+        let end = offset.saturating_add(len);
+
+        let mut out = Vec::new();
+        for entry in &index.entries {
+            let entry_end = entry.uncompressed_offset + entry.uncompressed_len;
+            if entry_end <= offset {
+                continue;
+            }
+            if entry.uncompressed_offset >= end {
+                break;
+            }
+
+            file.seek(SeekFrom::Start(entry.compressed_offset))?;
+            let mut compressed = vec![0u8; entry.compressed_len as usize];
+            file.read_exact(&mut compressed)?;
+            let block = decompress_block(&compressed)?;
+
+            let start_in_block = offset.saturating_sub(entry.uncompressed_offset) as usize;
+            let end_in_block = (end.min(entry_end) - entry.uncompressed_offset) as usize;
+            out.extend_from_slice(&block[start_in_block..end_in_block]);
+        }
+
+        Ok(out)
+    }
+
+    /// Appends `data` as one or more new blocks, extending the index without
+    /// touching or re-deflating any existing block.
+    pub fn append(&self, data: &[u8]) -> Result<(), io::Error> {
+        let mut index = self.read_index()?;
+        let mut data_offset = index.uncompressed_len();
+        let mut compressed_offset = index.body_len();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        // Drop the old trailing index; new blocks are appended right after
+        // the last existing block's compressed bytes.
+        file.set_len(compressed_offset)?;
+        file.seek(SeekFrom::Start(compressed_offset))?;
+
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let compressed = compress_block(chunk)?;
+            file.write_all(&compressed)?;
+            index.entries.push(BlockIndexEntry {
+                uncompressed_offset: data_offset,
+                uncompressed_len: chunk.len() as u64,
+                compressed_offset,
+                compressed_len: compressed.len() as u64,
+            });
+            data_offset += chunk.len() as u64;
+            compressed_offset += compressed.len() as u64;
+        }
+
+        write_index(&mut file, &index)
+    }
+
+    /// Total uncompressed length of the file's payload.
+    pub fn len(&self) -> Result<u64, io::Error> {
+        Ok(self.read_index()?.uncompressed_len())
+    }
+
+    /// Whether the file has no payload yet.
+    pub fn is_empty(&self) -> Result<bool, io::Error> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// Appends `index`'s serialized form and its length to `file`, which must
+/// already be positioned right after the last block's compressed bytes.
+fn write_index(file: &mut File, index: &BlockIndex) -> Result<(), io::Error> {
+    let index_bytes = index.to_bytes();
+    file.write_all(&index_bytes)?;
+    file.write_all(&(index_bytes.len() as u64).to_le_bytes())?;
+    Ok(())
+}
+
+/// Recompresses only the block(s) overlapping `[pos, pos + hash.len())` with
+/// the given bytes. Every other block's compressed bytes are copied across
+/// unchanged (never decompressed), so the compression work scales with the
+/// size of the affected region; the file's block sequence and index are
+/// still written back in full from offset 0.
+///
+/// This is the block-format counterpart to
+/// [`write_string_file_gz`](crate::write_string_file_gz), used when the
+/// target file already holds (or should hold) a [`BlockCompressedFile`].
+pub fn patch_block_file(file: &mut File, hash: &str, pos: u64) -> Result<(), io::Error> {
+    let mut index = if file.metadata()?.len() == 0 {
+        BlockIndex::default()
+    } else {
+        read_index_from_file(file)?
+    };
+
+    let hash_bytes = hash.as_bytes();
+    let patch_end = pos + hash_bytes.len() as u64;
+
+    // Extend with zero-filled blocks if the patch reaches past current content.
+    let mut current_len = index.uncompressed_len();
+    while patch_end > current_len {
+        let chunk_len = (patch_end - current_len).min(BLOCK_SIZE as u64);
+        index.entries.push(BlockIndexEntry {
+            uncompressed_offset: current_len,
+            uncompressed_len: chunk_len,
+            compressed_offset: 0,
+            compressed_len: 0,
+        });
+        current_len += chunk_len;
+    }
+
+    if index.entries.is_empty() {
+        // Empty hash patched at position 0 of a brand-new file: the extend
+        // loop above had nothing to add, so there are no blocks to touch.
+        let compressed_offset = index.body_len();
+        file.set_len(compressed_offset)?;
+        return write_index(file, &index);
+    }
+
+    let first = index
+        .entries
+        .iter()
+        .position(|e| e.uncompressed_offset + e.uncompressed_len > pos)
+        .unwrap_or(0);
+    let last = index
+        .entries
+        .iter()
+        .rposition(|e| e.uncompressed_offset < patch_end)
+        .unwrap_or(index.entries.len() - 1);
+
+    // Decompress only the blocks the patch actually touches, patch the
+    // combined plaintext in memory, then recompress it along the same block
+    // boundaries.
+    let mut affected_plain = Vec::new();
+    for entry in &index.entries[first..=last] {
+        if entry.compressed_len == 0 {
+            affected_plain.resize(affected_plain.len() + entry.uncompressed_len as usize, 0);
+        } else {
+            file.seek(SeekFrom::Start(entry.compressed_offset))?;
+            let mut compressed = vec![0u8; entry.compressed_len as usize];
+            file.read_exact(&mut compressed)?;
+            affected_plain.extend_from_slice(&decompress_block(&compressed)?);
+        }
+    }
+
+    let region_start = index.entries[first].uncompressed_offset;
+    let start_in_region = (pos - region_start) as usize;
+    let end_in_region = (patch_end - region_start) as usize;
+    affected_plain[start_in_region..end_in_region].copy_from_slice(hash_bytes);
+
+    let mut recompressed_blocks = Vec::with_capacity(last - first + 1);
+    let mut local_offset = 0usize;
+    for entry in &index.entries[first..=last] {
+        let len = entry.uncompressed_len as usize;
+        recompressed_blocks.push(compress_block(&affected_plain[local_offset..local_offset + len])?);
+        local_offset += len;
+    }
+
+    // Unaffected blocks' raw compressed bytes, read once before the file is
+    // truncated and rewritten.
+    let mut prefix_blocks = Vec::with_capacity(first);
+    for entry in &index.entries[..first] {
+        prefix_blocks.push(read_raw_block(file, entry)?);
+    }
+    let mut suffix_blocks = Vec::with_capacity(index.entries.len() - last - 1);
+    for entry in &index.entries[last + 1..] {
+        suffix_blocks.push(read_raw_block(file, entry)?);
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut new_entries = Vec::with_capacity(index.entries.len());
+    let mut compressed_offset = 0u64;
+
+    let all_blocks = index.entries[..first]
+        .iter()
+        .zip(&prefix_blocks)
+        .chain(index.entries[first..=last].iter().zip(&recompressed_blocks))
+        .chain(index.entries[last + 1..].iter().zip(&suffix_blocks));
+    for (entry, compressed) in all_blocks {
+        file.write_all(compressed)?;
+        new_entries.push(BlockIndexEntry {
+            compressed_offset,
+            compressed_len: compressed.len() as u64,
+            ..*entry
+        });
+        compressed_offset += compressed.len() as u64;
+    }
+
+    index.entries = new_entries;
+    file.set_len(compressed_offset)?;
+    write_index(file, &index)
+}
+
+/// Reads a block's raw compressed bytes without decompressing them.
+fn read_raw_block(file: &mut File, entry: &BlockIndexEntry) -> Result<Vec<u8>, io::Error> {
+    file.seek(SeekFrom::Start(entry.compressed_offset))?;
+    let mut compressed = vec![0u8; entry.compressed_len as usize];
+    file.read_exact(&mut compressed)?;
+    Ok(compressed)
+}
+
+/// Reads the trailing index from a file whose cursor position is ignored;
+/// restores no particular cursor position afterward.
+fn read_index_from_file(file: &mut File) -> Result<BlockIndex, io::Error> {
+    let total_len = file.metadata()?.len();
+    if total_len < 8 {
+        return Ok(BlockIndex::default());
+    }
+
+    file.seek(SeekFrom::End(-8))?;
+    let mut index_len_buf = [0u8; 8];
+    file.read_exact(&mut index_len_buf)?;
+    let index_len = u64::from_le_bytes(index_len_buf);
+
+    file.seek(SeekFrom::End(-8 - index_len as i64))?;
+    let mut index_bytes = vec![0u8; index_len as usize];
+    file.read_exact(&mut index_bytes)?;
+
+    BlockIndex::from_bytes(&index_bytes)
+}
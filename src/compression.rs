@@ -0,0 +1,172 @@
+//! Compression backends supported by [`CompressedFile`](crate::CompressedFile).
+//!
+//! Mirrors the way the `zip` crate lets callers pick a codec per entry:
+//! a [`CompressionMethod`] selects the algorithm and a companion
+//! [`CompressionOptions`] carries the method alongside a compression `level`.
+//! A one-byte tag identifying the method is written at the start of the file
+//! when it is created, so [`CompressedFile::open_for_read`](crate::CompressedFile::open_for_read)
+//! can pick the matching decoder without the caller having to repeat it.
+
+use std::io;
+use std::io::{Read, Write};
+
+use bzip2::read::BzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::Compression as GzCompression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+/// Codec used to compress a [`CompressedFile`](crate::CompressedFile).
+///
+/// `Stored` performs no compression at all, which is useful for data that is
+/// already compressed (or encrypted) and would not shrink further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMethod {
+    Stored,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl CompressionMethod {
+    /// One-byte tag this method is identified by in a file's header.
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            CompressionMethod::Stored => 0,
+            CompressionMethod::Gzip => 1,
+            CompressionMethod::Zstd => 2,
+            CompressionMethod::Bzip2 => 3,
+        }
+    }
+
+    /// Recovers a `CompressionMethod` from a tag byte previously written by [`tag`](Self::tag).
+    pub(crate) fn from_tag(tag: u8) -> Result<Self, io::Error> {
+        match tag {
+            0 => Ok(CompressionMethod::Stored),
+            1 => Ok(CompressionMethod::Gzip),
+            2 => Ok(CompressionMethod::Zstd),
+            3 => Ok(CompressionMethod::Bzip2),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown compression method tag: {other}"),
+            )),
+        }
+    }
+
+    /// Infers the compression method from a file's leading bytes, the way
+    /// `ouch` sniffs content instead of trusting a file's name or a header
+    /// tag we didn't write ourselves. Falls back to `Stored` (passthrough)
+    /// when `prefix` doesn't match a known magic number.
+    pub(crate) fn sniff(prefix: &[u8]) -> CompressionMethod {
+        const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+        const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+        const BZIP2_MAGIC: [u8; 3] = *b"BZh";
+
+        if prefix.starts_with(&GZIP_MAGIC) {
+            CompressionMethod::Gzip
+        } else if prefix.starts_with(&ZSTD_MAGIC) {
+            CompressionMethod::Zstd
+        } else if prefix.starts_with(&BZIP2_MAGIC) {
+            CompressionMethod::Bzip2
+        } else {
+            CompressionMethod::Stored
+        }
+    }
+
+    /// Wraps `inner` in the decoder matching this method.
+    pub(crate) fn decoder(self, inner: impl Read + 'static) -> Box<dyn Read> {
+        match self {
+            CompressionMethod::Stored => Box::new(inner),
+            CompressionMethod::Gzip => Box::new(GzDecoder::new(inner)),
+            CompressionMethod::Zstd => {
+                Box::new(zstd::stream::read::Decoder::new(inner).expect("zstd decoder init"))
+            }
+            CompressionMethod::Bzip2 => Box::new(BzDecoder::new(inner)),
+        }
+    }
+}
+
+/// Compression codec and level applied when writing a [`CompressedFile`](crate::CompressedFile).
+///
+/// The `level` meaning depends on `method`: it is ignored for `Stored`, is a
+/// 0-9 gzip/bzip2 level for `Gzip`/`Bzip2`, and a 1-22 zstd level for `Zstd`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionOptions {
+    pub method: CompressionMethod,
+    pub level: u32,
+}
+
+impl Default for CompressionOptions {
+    /// Gzip at the same default level the crate used before compression
+    /// became pluggable.
+    fn default() -> Self {
+        Self {
+            method: CompressionMethod::Gzip,
+            level: GzCompression::default().level(),
+        }
+    }
+}
+
+impl CompressionOptions {
+    /// Wraps `inner` in the encoder matching this method and level.
+    ///
+    /// Returns the concrete [`Encoder`] rather than a boxed trait object so
+    /// callers that need to reclaim `inner` once compression is done (to
+    /// patch a header, for instance) can call [`Encoder::finish`].
+    pub(crate) fn build_encoder<W: Write>(self, inner: W) -> Encoder<W> {
+        match self.method {
+            CompressionMethod::Stored => Encoder::Stored(inner),
+            CompressionMethod::Gzip => {
+                Encoder::Gzip(GzEncoder::new(inner, GzCompression::new(self.level)))
+            }
+            CompressionMethod::Zstd => Encoder::Zstd(
+                zstd::stream::write::Encoder::new(inner, self.level as i32)
+                    .expect("zstd encoder init"),
+            ),
+            CompressionMethod::Bzip2 => {
+                Encoder::Bzip2(BzEncoder::new(inner, bzip2::Compression::new(self.level)))
+            }
+        }
+    }
+}
+
+/// A compressor for one of the methods in [`CompressionMethod`], generic over
+/// its underlying writer so it can be finished to reclaim that writer.
+pub(crate) enum Encoder<W: Write> {
+    Stored(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Bzip2(BzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Flushes any buffered compressed bytes and returns the underlying writer.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Stored(w) => Ok(w),
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Zstd(e) => e.finish(),
+            Encoder::Bzip2(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Stored(w) => w.write(buf),
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+            Encoder::Bzip2(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Stored(w) => w.flush(),
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+            Encoder::Bzip2(e) => e.flush(),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Encrypt-then-MAC layer used by [`CompressedFile`](crate::CompressedFile) to
+//! add at-rest confidentiality and tamper detection on top of the gzip stream.
+//!
+//! The on-disk layout produced by this module is:
+//!
+//! ```text
+//! [ 16-byte salt ][ 16-byte IV ][ AES-256-CTR ciphertext ][ 32-byte HMAC-SHA256 tag ]
+//! ```
+//!
+//! The key is derived from the caller's passphrase and the salt with
+//! PBKDF2-HMAC-SHA256, mirroring the approach the `zip` crate uses for its AES
+//! entries. The tag is computed over the ciphertext only (encrypt-then-MAC),
+//! so it can be verified before a single byte of plaintext is produced.
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+
+use aes::Aes256;
+use ctr::Ctr128BE;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+
+pub(crate) const SALT_LEN: usize = 16;
+pub(crate) const IV_LEN: usize = 16;
+pub(crate) const TAG_LEN: usize = 32;
+pub(crate) const HEADER_LEN: usize = SALT_LEN + IV_LEN;
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+const KEY_LEN: usize = 32;
+
+type Aes256Ctr = Ctr128BE<Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// Derives a 256-bit key from `passphrase` and `salt` using PBKDF2-HMAC-SHA256,
+/// mixing `context` into the salt so different purposes never land on the
+/// same key even when given the same passphrase and salt.
+fn derive_key(passphrase: &str, salt: &[u8], context: &[u8]) -> [u8; KEY_LEN] {
+    let mut salted = Vec::with_capacity(salt.len() + context.len());
+    salted.extend_from_slice(salt);
+    salted.extend_from_slice(context);
+
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), &salted, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Derives independent AES-CTR encryption and HMAC authentication keys from
+/// the same passphrase and salt, so compromising one primitive's key doesn't
+/// hand over the other's.
+fn derive_keys(passphrase: &str, salt: &[u8]) -> ([u8; KEY_LEN], [u8; KEY_LEN]) {
+    let enc_key = derive_key(passphrase, salt, b"dit-file-encryptor-enc");
+    let mac_key = derive_key(passphrase, salt, b"dit-file-encryptor-mac");
+    (enc_key, mac_key)
+}
+
+/// Generates a fresh random salt and IV, writes the plaintext header
+/// `[salt][iv]` that a reader needs to reconstruct the key and keystream,
+/// and returns them so the caller can encrypt against the same values.
+///
+/// Must be called again (onto a freshly truncated file) every time a file is
+/// reopened for writing: reusing a salt/IV pair to encrypt new plaintext
+/// under the same derived key and keystream is a two-time-pad break.
+pub(crate) fn write_header(file: &mut File) -> Result<([u8; SALT_LEN], [u8; IV_LEN]), io::Error> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut iv = [0u8; IV_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut iv);
+    file.write_all(&salt)?;
+    file.write_all(&iv)?;
+    Ok((salt, iv))
+}
+
+/// Wraps a writer, encrypting every byte written to it with AES-256-CTR and
+/// authenticating the resulting ciphertext with HMAC-SHA256.
+///
+/// The authentication tag is appended once the writer is dropped, matching
+/// how [`flate2::write::GzEncoder`] finalizes itself on drop.
+pub(crate) struct EncryptWriter<W: Write> {
+    inner: Option<W>,
+    cipher: Aes256Ctr,
+    mac: HmacSha256,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    pub(crate) fn new(inner: W, passphrase: &str, salt: &[u8], iv: &[u8]) -> Self {
+        let (enc_key, mac_key) = derive_keys(passphrase, salt);
+        let cipher = Aes256Ctr::new(enc_key.as_slice().into(), iv.into());
+        let mac = HmacSha256::new_from_slice(&mac_key).expect("HMAC-SHA256 accepts any key length");
+        Self {
+            inner: Some(inner),
+            cipher,
+            mac,
+        }
+    }
+
+    /// Flushes any buffered ciphertext and appends the HMAC tag, returning
+    /// the wrapped writer. Also invoked by `Drop` on a best-effort basis if
+    /// the caller never calls this directly.
+    fn finish_mut(&mut self) -> Result<W, io::Error> {
+        let mut inner = self
+            .inner
+            .take()
+            .ok_or_else(|| io::Error::other("encrypted writer already finished"))?;
+        inner.flush()?;
+        let mac = std::mem::replace(
+            &mut self.mac,
+            HmacSha256::new_from_slice(&[0u8; KEY_LEN]).expect("HMAC-SHA256 accepts any key length"),
+        );
+        let tag = mac.finalize().into_bytes();
+        inner.write_all(&tag)?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = self
+            .inner
+            .as_mut()
+            .ok_or_else(|| io::Error::other("write after finish"))?;
+        let mut ciphertext = buf.to_vec();
+        self.cipher.apply_keystream(&mut ciphertext);
+        self.mac.update(&ciphertext);
+        inner.write_all(&ciphertext)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Drop for EncryptWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finish_mut();
+        }
+    }
+}
+
+/// Reads an encrypted body (the bytes that follow the `[salt][iv]` header),
+/// verifies its HMAC tag in constant time, and returns the decrypted
+/// plaintext. No plaintext is returned if the tag does not match.
+pub(crate) fn decrypt_and_verify(
+    mut source: impl Read,
+    passphrase: &str,
+    salt: &[u8],
+    iv: &[u8],
+) -> Result<Vec<u8>, io::Error> {
+    let mut body = Vec::new();
+    source.read_to_end(&mut body)?;
+    if body.len() < TAG_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "encrypted file is truncated",
+        ));
+    }
+
+    let tag_offset = body.len() - TAG_LEN;
+    let (ciphertext, tag) = body.split_at(tag_offset);
+
+    let (enc_key, mac_key) = derive_keys(passphrase, salt);
+    let mut mac = HmacSha256::new_from_slice(&mac_key).expect("HMAC-SHA256 accepts any key length");
+    mac.update(ciphertext);
+    mac.verify_slice(tag).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "authentication tag mismatch: file may have been tampered with",
+        )
+    })?;
+
+    let mut plaintext = ciphertext.to_vec();
+    let mut cipher = Aes256Ctr::new(enc_key.as_slice().into(), iv.into());
+    cipher.apply_keystream(&mut plaintext);
+    Ok(plaintext)
+}
@@ -0,0 +1,121 @@
+//! CRC32 integrity header for [`CompressedFile`](crate::CompressedFile).
+//!
+//! Every plain (non-encrypted) file carries a fixed-size header right after
+//! the one-byte compression-method tag written by the `compression` module:
+//!
+//! ```text
+//! [ method tag: 1 byte ][ CRC32 of uncompressed bytes: 4 bytes LE ][ compressed body ]
+//! ```
+//!
+//! The CRC is only known once every byte has been written, so [`CrcWriter`]
+//! reserves the 4-byte slot up front and seeks back to patch it in once the
+//! compressor underneath has finished.
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crc32fast::Hasher;
+
+use crate::compression::Encoder;
+
+pub(crate) const CRC_LEN: usize = 4;
+const CRC_OFFSET: u64 = 1; // right after the 1-byte method tag
+
+/// Wraps the body [`Encoder`] for a file being written, hashing the
+/// uncompressed bytes as they arrive and patching the CRC32 header once the
+/// compressor is finished.
+pub(crate) struct CrcWriter {
+    inner: Option<Encoder<File>>,
+    hasher: Hasher,
+}
+
+impl CrcWriter {
+    pub(crate) fn new(inner: Encoder<File>) -> Self {
+        Self {
+            inner: Some(inner),
+            hasher: Hasher::new(),
+        }
+    }
+
+    /// Finishes the compressed stream and patches the CRC32 header.
+    pub(crate) fn finish(mut self) -> Result<(), io::Error> {
+        self.finish_mut()
+    }
+
+    fn finish_mut(&mut self) -> Result<(), io::Error> {
+        let Some(encoder) = self.inner.take() else {
+            return Ok(());
+        };
+        let mut file = encoder.finish()?;
+        let hasher = std::mem::replace(&mut self.hasher, Hasher::new());
+        let crc = hasher.finalize();
+        file.seek(SeekFrom::Start(CRC_OFFSET))?;
+        file.write_all(&crc.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl Write for CrcWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        match self.inner.as_mut() {
+            Some(inner) => inner.write(buf),
+            None => Err(io::Error::other("write after finish")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for CrcWriter {
+    fn drop(&mut self) {
+        let _ = self.finish_mut();
+    }
+}
+
+/// Wraps a decompressed [`Read`], hashing bytes as they stream out and
+/// comparing against the CRC32 recorded in a file's header once the
+/// underlying reader is exhausted, instead of requiring a second full pass.
+pub(crate) struct CrcCheckedReader<R: Read> {
+    inner: R,
+    hasher: Hasher,
+    expected: u32,
+}
+
+impl<R: Read> CrcCheckedReader<R> {
+    pub(crate) fn new(inner: R, expected: u32) -> Self {
+        Self {
+            inner,
+            hasher: Hasher::new(),
+            expected,
+        }
+    }
+}
+
+impl<R: Read> Read for CrcCheckedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 {
+            let hasher = std::mem::replace(&mut self.hasher, Hasher::new());
+            let crc = hasher.finalize();
+            if crc != self.expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "CRC32 mismatch: expected {:08x}, computed {:08x}",
+                        self.expected, crc
+                    ),
+                ));
+            }
+            return Ok(0);
+        }
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
@@ -1,36 +1,54 @@
 use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 
-/// Represents a file that supports gzip compression and decompression.
+mod block;
+mod compression;
+mod crypto;
+mod integrity;
+
+pub use block::BlockCompressedFile;
+pub use compression::{CompressionMethod, CompressionOptions};
+
+/// Represents a file compressed with a configurable codec (see
+/// [`CompressionMethod`]), supporting both plain and AES-256-CTR-encrypted
+/// reads and writes.
 pub struct CompressedFile {
     /// Path to the file on the filesystem.
     path: PathBuf,
+    /// Codec and level used when (re)writing the file's body.
+    options: CompressionOptions,
 }
 
 impl CompressedFile {
     /// Creates a new compressed file at the specified path.
     ///
-    /// If the file already exists, its content will be truncated.
+    /// If the file already exists, its content will be truncated. A one-byte
+    /// tag identifying `options.method` is written immediately, followed by a
+    /// zeroed CRC32 placeholder that [`open_for_write`](Self::open_for_write)
+    /// and [`append_to_file`](Self::append_to_file) patch in once the body
+    /// has been written, so later reads can pick the right decoder and
+    /// verify integrity without being told either again.
     ///
     /// # Arguments
     ///
     /// * `path` - A `PathBuf` that specifies the path where the file will be created.
+    /// * `options` - The compression method and level the file's body will be written with.
     ///
     /// # Returns
     ///
     /// Returns a `Result` containing a new `CompressedFile` instance if successful,
     /// or an `io::Error` if the file cannot be created.
-    pub fn create_file(path: PathBuf) -> Result<CompressedFile, io::Error> {
-        File::create(path.clone())?;
-        Ok(Self {
-            path,
-        })
+    pub fn create_file(path: PathBuf, options: CompressionOptions) -> Result<CompressedFile, io::Error> {
+        let mut file = File::create(&path)?;
+        file.write_all(&[options.method.tag()])?;
+        file.write_all(&[0u8; integrity::CRC_LEN])?;
+        Ok(Self { path, options })
     }
 
     /// Creates an object compressed from the specified path.
@@ -38,50 +56,136 @@ impl CompressedFile {
     /// # Arguments
     ///
     /// * `path` - A string slice that specifies the path where the file will be created.
+    /// * `options` - The compression method and level used when (re)writing the file's body.
     ///
     /// # Returns
     ///
     /// Returns a `CompressedFile` instance.
-    pub fn new(path: PathBuf) -> Self{
-        Self {
-            path
-        }
+    pub fn new(path: PathBuf, options: CompressionOptions) -> Self {
+        Self { path, options }
+    }
+
+    /// Reads the one-byte method tag and four-byte CRC32 header shared by
+    /// every plain `CompressedFile`, leaving `file`'s cursor at the start of
+    /// the compressed body.
+    fn read_header(file: &mut File) -> Result<(CompressionMethod, u32), io::Error> {
+        let mut tag = [0u8; 1];
+        file.read_exact(&mut tag)?;
+        let method = CompressionMethod::from_tag(tag[0])?;
+
+        let mut crc_buf = [0u8; integrity::CRC_LEN];
+        file.read_exact(&mut crc_buf)?;
+        Ok((method, u32::from_le_bytes(crc_buf)))
     }
 
     /// Opens the file for reading and decompresses its content on the fly.
     ///
+    /// The compression method is read from the one-byte tag written at the
+    /// start of the file, so the caller does not need to know it in advance.
+    ///
     /// # Returns
     ///
     /// Returns a `Result` containing a `Box<dyn Read>` if successful,
-    /// or an `io::Error` if the file cannot be opened or read.
+    /// or an `io::Error` if the file cannot be opened or its method tag is unrecognized.
     pub fn open_for_read(&self) -> Result<Box<dyn Read>, io::Error> {
-        let file = File::open(&self.path)?;
-        Ok(Box::new(GzDecoder::new(file)))
+        let mut file = File::open(&self.path)?;
+        let (method, _crc) = Self::read_header(&mut file)?;
+        Ok(method.decoder(file))
     }
 
-    /// Opens the file for writing and compresses its content on the fly.
+    /// Opens the file for reading like [`open_for_read`](Self::open_for_read),
+    /// but hashes the decompressed bytes as they stream out and errors once
+    /// the stream is exhausted if the CRC32 recorded in the header doesn't
+    /// match, instead of requiring a separate full pass via [`verify`](Self::verify).
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `append` - A boolean indicating whether to append to the existing file (`true`)
-    ///   or overwrite it (`false`).
+    /// Returns a `Result` containing a `Box<dyn Read>` if successful,
+    /// or an `io::Error` if the file cannot be opened, its method tag is
+    /// unrecognized, or the CRC32 check fails.
+    pub fn open_for_read_checked(&self) -> Result<Box<dyn Read>, io::Error> {
+        let mut file = File::open(&self.path)?;
+        let (method, expected_crc) = Self::read_header(&mut file)?;
+        let decoder = method.decoder(file);
+        Ok(Box::new(integrity::CrcCheckedReader::new(decoder, expected_crc)))
+    }
+
+    /// Opens an arbitrary file for reading, inferring its compression method
+    /// from its leading bytes instead of trusting the one-byte tag the rest
+    /// of this crate writes (the file may not have one at all — it might be
+    /// a plain `.gz`/`.zst`/`.bz2` produced by another tool, or not be
+    /// compressed at all).
+    ///
+    /// Recognizes the gzip (`1f 8b`), zstd (`28 b5 2f fd`), and bzip2 (`BZh`)
+    /// magic numbers, falling back to a passthrough reader otherwise.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a `Box<dyn Read>` if successful,
+    /// or an `io::Error` if the file cannot be opened or read.
+    pub fn open_auto(path: PathBuf) -> Result<Box<dyn Read>, io::Error> {
+        let mut file = File::open(&path)?;
+        let mut prefix = [0u8; 4];
+        let read = file.read(&mut prefix)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let method = CompressionMethod::sniff(&prefix[..read]);
+        Ok(method.decoder(file))
+    }
+
+    /// Decompresses the whole file and checks its content against the CRC32
+    /// recorded in the header.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the CRC32 matches, `Ok(false)` if it doesn't,
+    /// or an `io::Error` if the file cannot be opened, read, or its method
+    /// tag is unrecognized.
+    pub fn verify(&self) -> Result<bool, io::Error> {
+        let mut file = File::open(&self.path)?;
+        let (method, expected_crc) = Self::read_header(&mut file)?;
+        let mut reader = method.decoder(file);
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(hasher.finalize() == expected_crc)
+    }
+
+    /// Opens the file for writing and compresses its content on the fly.
+    ///
+    /// Writes the one-byte method tag and a zeroed CRC32 placeholder ahead
+    /// of the compressed body, using `self.options`; the CRC32 placeholder
+    /// is patched with the hash of the uncompressed bytes once the returned
+    /// writer is finished or dropped.
     ///
     /// # Returns
     ///
     /// Returns a `Result` containing a `Box<dyn Write>` if successful,
     /// or an `io::Error` if the file cannot be opened or written.
     pub fn open_for_write(&self) -> Result<Box<dyn Write>, io::Error> {
-        let file = OpenOptions::new()
+        let mut file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&self.path)?;
-        Ok(Box::new(GzEncoder::new(file, Compression::default())))
+        file.write_all(&[self.options.method.tag()])?;
+        file.write_all(&[0u8; integrity::CRC_LEN])?;
+        let encoder = self.options.build_encoder(file);
+        Ok(Box::new(integrity::CrcWriter::new(encoder)))
     }
 
     /// Appends the given text to the compressed file.
     ///
     /// This method reads the existing content of the file (if any), combines it with the new text,
-    /// and writes the entire content back to the file using gzip compression.
+    /// and writes the entire content back to the file using `self.options`.
     ///
     /// # Parameters
     ///
@@ -96,7 +200,7 @@ impl CompressedFile {
     ///
     /// - If the file does not exist, it creates a new compressed file with the given text
     /// - If the file exists, it reads the existing content, appends the new text, and rewrites the file
-    /// - Uses default gzip compression
+    /// - Uses `self.options` for the rewritten body
     ///
     /// # Note
     ///
@@ -111,19 +215,105 @@ impl CompressedFile {
 
         let combined_content = [existing_content, text.to_vec()].concat();
 
-        let file = File::create(&self.path)?;
-        let mut writer = GzEncoder::new(file, Compression::default());
+        let mut file = File::create(&self.path)?;
+        file.write_all(&[self.options.method.tag()])?;
+        file.write_all(&[0u8; integrity::CRC_LEN])?;
+        let encoder = self.options.build_encoder(file);
+        let mut writer = integrity::CrcWriter::new(encoder);
         writer.write_all(&combined_content)?;
         writer.finish()?;
 
         Ok(())
     }
+
+    /// Creates a new encrypted, gzip-compressed file at the specified path.
+    ///
+    /// This only creates an empty file; no passphrase is needed yet because
+    /// no header exists until [`open_for_write_encrypted`](Self::open_for_write_encrypted)
+    /// writes one along with a fresh salt and IV on its first call.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A `PathBuf` that specifies the path where the file will be created.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a new `CompressedFile` instance if successful,
+    /// or an `io::Error` if the file cannot be created.
+    pub fn create_encrypted(path: PathBuf) -> Result<CompressedFile, io::Error> {
+        File::create(&path)?;
+        Ok(Self {
+            path,
+            options: CompressionOptions::default(),
+        })
+    }
+
+    /// Opens the encrypted file for reading and transparently verifies, decrypts,
+    /// and decompresses its content.
+    ///
+    /// The HMAC-SHA256 authentication tag is recomputed and compared in
+    /// constant time before any plaintext is returned, so tampering with the
+    /// ciphertext is detected instead of silently yielding garbage.
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - The passphrase the file was encrypted with.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a `Box<dyn Read>` if successful,
+    /// or an `io::Error` if the file cannot be opened, is truncated, or fails
+    /// authentication.
+    pub fn open_for_read_encrypted(&self, passphrase: &str) -> Result<Box<dyn Read>, io::Error> {
+        let mut file = File::open(&self.path)?;
+        let mut header = [0u8; crypto::HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let (salt, iv) = header.split_at(crypto::SALT_LEN);
+
+        let compressed = crypto::decrypt_and_verify(file, passphrase, salt, iv)?;
+        Ok(Box::new(GzDecoder::new(Cursor::new(compressed))))
+    }
+
+    /// Opens the encrypted file for writing and transparently compresses and
+    /// encrypts its content.
+    ///
+    /// Content is compressed with gzip first and the resulting (incompressible)
+    /// ciphertext is produced with AES-256-CTR, then authenticated with an
+    /// HMAC-SHA256 tag appended once the caller is done writing.
+    ///
+    /// Like [`open_for_write`](Self::open_for_write), this truncates the
+    /// file; a fresh salt and IV are generated and written every call so that
+    /// calling this more than once on the same `CompressedFile` never reuses
+    /// a salt/IV pair to encrypt two different plaintexts under the same
+    /// keystream.
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - The passphrase the encryption key is derived from.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a `Box<dyn Write>` if successful,
+    /// or an `io::Error` if the file cannot be opened or its header written.
+    pub fn open_for_write_encrypted(&self, passphrase: &str) -> Result<Box<dyn Write>, io::Error> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.path)?;
+        let (salt, iv) = crypto::write_header(&mut file)?;
+
+        let encrypt_writer = crypto::EncryptWriter::new(file, passphrase, &salt, &iv);
+        Ok(Box::new(GzEncoder::new(encrypt_writer, Compression::default())))
+    }
 }
 
-/// Writes a hash to a specific position in a gzip-compressed file while preserving the rest of the content.
+/// Writes a hash to a specific position in a block-compressed file while preserving the rest of the content.
 ///
-/// This function reads the entire compressed file, modifies the content at the specified position,
-/// and then rewrites the entire file with the modifications.
+/// Unlike a single gzip stream, the file's payload is stored as the
+/// independent, fixed-size blocks described in [`block`], so only the
+/// block(s) overlapping `pos..pos + hash.len()` need to be decompressed and
+/// recompressed; every other block's compressed bytes are carried over as-is.
 ///
 /// # Parameters
 ///
@@ -138,38 +328,16 @@ impl CompressedFile {
 ///
 /// # Behavior
 ///
-/// - Reads the entire content of the gzip-compressed file
+/// - Decompresses only the block(s) overlapping the write, not the whole file
 /// - Replaces the content at the specified position with the new hash
-/// - Extends the content if the position is beyond the current file length
-/// - Rewrites the entire file, maintaining the gzip compression
+/// - Extends the content with zero-filled blocks if the position is beyond the current length
+/// - Rewrites the file's block sequence and index, but never re-deflates unaffected blocks
 ///
 /// # Notes
 ///
-/// - This method is less efficient for very large files as it reads and rewrites the entire file
 /// - The file must be opened with both read and write permissions
 pub fn write_string_file_gz(hash: String, file: &mut File, pos: u64) -> Result<(), io::Error> {
-    let mut existing_content = Vec::new();
-
-    if file.metadata()?.len() > 0 {
-        let mut gz_reader = GzDecoder::new(file.try_clone()?);
-        gz_reader.read_to_end(&mut existing_content)?;
-    }
-
-    let hash_bytes = hash.as_bytes();
-
-    if pos as usize + hash_bytes.len() > existing_content.len() {
-        existing_content.resize(pos as usize + hash_bytes.len(), 0);
-    }
-
-    existing_content[pos as usize..pos as usize + hash_bytes.len()].copy_from_slice(hash_bytes);
-
-    file.seek(SeekFrom::Start(0))?;
-
-    let mut gz_writer = GzEncoder::new(file, Compression::default());
-    gz_writer.write_all(&existing_content)?;
-    gz_writer.finish()?;
-
-    Ok(())
+    block::patch_block_file(file, &hash, pos)
 }
 
 #[cfg(test)]
@@ -192,7 +360,7 @@ mod tests {
             fs::remove_file(&path).unwrap();
         }
 
-        let compressed_file = CompressedFile::create_file(path.clone());
+        let compressed_file = CompressedFile::create_file(path.clone(), CompressionOptions::default());
         assert!(compressed_file.is_ok());
         assert!(path.exists());
     }
@@ -203,7 +371,7 @@ mod tests {
         let content = b"Hello, compressed world!";
 
         // Write to the compressed file
-        let compressed_file = CompressedFile::create_file(path.clone()).unwrap();
+        let compressed_file = CompressedFile::create_file(path.clone(), CompressionOptions::default()).unwrap();
         {
             let mut writer = compressed_file.open_for_write().unwrap();
             writer.write_all(content).unwrap();
@@ -218,6 +386,93 @@ mod tests {
         assert_eq!(content, &decompressed_content[..]);
     }
 
+    #[test]
+    fn test_write_and_read_file_with_each_compression_method() {
+        for method in [
+            CompressionMethod::Stored,
+            CompressionMethod::Gzip,
+            CompressionMethod::Zstd,
+            CompressionMethod::Bzip2,
+        ] {
+            let path = create_temp_file(&format!("test_write_and_read_{method:?}.bin"));
+            let content = b"Hello, pluggable compression!";
+            let options = CompressionOptions { method, level: 6 };
+
+            let compressed_file = CompressedFile::create_file(path.clone(), options).unwrap();
+            {
+                let mut writer = compressed_file.open_for_write().unwrap();
+                writer.write_all(content).unwrap();
+                writer.flush().unwrap();
+            }
+
+            let mut reader = compressed_file.open_for_read().unwrap();
+            let mut decompressed_content = Vec::new();
+            reader.read_to_end(&mut decompressed_content).unwrap();
+
+            assert_eq!(content, &decompressed_content[..], "method {method:?} roundtrip failed");
+        }
+    }
+
+    #[test]
+    fn test_verify_passes_for_untampered_file() {
+        let path = create_temp_file("test_verify_ok.gz");
+        let compressed_file = CompressedFile::create_file(path, CompressionOptions::default()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write().unwrap();
+            writer.write_all(b"trustworthy bytes").unwrap();
+        }
+
+        assert!(compressed_file.verify().unwrap());
+    }
+
+    #[test]
+    fn test_verify_fails_for_corrupted_body() {
+        let path = create_temp_file("test_verify_corrupted.gz");
+        let compressed_file = CompressedFile::create_file(path.clone(), CompressionOptions::default()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write().unwrap();
+            writer.write_all(b"trustworthy bytes").unwrap();
+        }
+
+        // Flip the last byte of the file, which falls within gzip's own
+        // trailing CRC32/ISIZE footer rather than its header fields (some of
+        // which tolerate arbitrary values without invalidating the stream).
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.seek(SeekFrom::Start(len - 1)).unwrap();
+        let mut byte = [0u8; 1];
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(len - 1)).unwrap();
+        file.write_all(&[byte[0] ^ 0xFF]).unwrap();
+
+        // The gzip decoder checks its own footer, so this can surface as a
+        // decode error rather than a CRC mismatch; either way the corruption
+        // must be caught.
+        let outcome = compressed_file.verify();
+        assert!(matches!(outcome, Err(_) | Ok(false)));
+    }
+
+    #[test]
+    fn test_open_for_read_checked_detects_crc_mismatch() {
+        let path = create_temp_file("test_checked_read_corrupted.gz");
+        let compressed_file = CompressedFile::create_file(path.clone(), CompressionOptions::default()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write().unwrap();
+            writer.write_all(b"trustworthy bytes").unwrap();
+        }
+
+        // Patch in a CRC that doesn't match the (untouched) body.
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(1)).unwrap();
+        file.write_all(&0xDEADBEEFu32.to_le_bytes()).unwrap();
+
+        let mut reader = compressed_file.open_for_read_checked().unwrap();
+        let mut buf = Vec::new();
+        let result = reader.read_to_end(&mut buf);
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_append_to_file() {
         let path = create_temp_file("test_append_to_file.gz");
@@ -226,7 +481,7 @@ mod tests {
 
         // Write the first line
         {
-            let compressed_file = CompressedFile::new(path.clone());
+            let compressed_file = CompressedFile::new(path.clone(), CompressionOptions::default());
             let mut writer = compressed_file.open_for_write().unwrap();
             writer.write_all(content1).unwrap();
             writer.flush().unwrap();
@@ -234,13 +489,13 @@ mod tests {
 
         // Append the second line using the new method
         {
-            let compressed_file = CompressedFile::new(path.clone());
+            let compressed_file = CompressedFile::new(path.clone(), CompressionOptions::default());
             compressed_file.append_to_file(content2).unwrap();
         }
 
         // Read and verify the content
         let mut decompressed_content = Vec::new();
-        let mut reader = CompressedFile::new(path).open_for_read().unwrap();
+        let mut reader = CompressedFile::new(path, CompressionOptions::default()).open_for_read().unwrap();
         reader.read_to_end(&mut decompressed_content).unwrap();
 
         let expected_content: Vec<u8> = Vec::from(content1)
@@ -255,7 +510,7 @@ mod tests {
     fn test_open_nonexistent_file_for_read() {
         let path = create_temp_file("nonexistent_file.gz");
 
-        let compressed_file = CompressedFile::new(path);
+        let compressed_file = CompressedFile::new(path, CompressionOptions::default());
         let result = compressed_file.open_for_read();
 
         assert!(result.is_err());
@@ -265,7 +520,7 @@ mod tests {
     fn test_open_nonexistent_file_for_write() {
         let path = create_temp_file("nonexistent_file_write.gz");
 
-        let compressed_file = CompressedFile::new(path.clone());
+        let compressed_file = CompressedFile::new(path.clone(), CompressionOptions::default());
         let result = compressed_file.open_for_write();
 
         // Writing should create the file even if it doesn't exist
@@ -275,19 +530,11 @@ mod tests {
 
     #[test]
     fn test_write_hash_file_gz() {
-        // Create a temporary file
-        let file_path = create_temp_file("test_hash_file.gz");
-        let file = File::create(&file_path).unwrap();
-
-        // Initial content to write
+        // Create a block-compressed file with some initial content.
+        let file_path = create_temp_file("test_hash_file.blk");
         let initial_content = b"Hello world, this is some initial content!";
-
-        // Compress initial content
-        {
-            let mut gz_writer = GzEncoder::new(&file, Compression::default());
-            gz_writer.write_all(initial_content).unwrap();
-            gz_writer.finish().unwrap();
-        }
+        let block_file = BlockCompressedFile::create(file_path.clone()).unwrap();
+        block_file.append(initial_content).unwrap();
 
         // Reopen the file for reading and writing
         let mut file = OpenOptions::new()
@@ -301,17 +548,258 @@ mod tests {
         write_string_file_gz(hash_to_write.clone(), &mut file, 6).unwrap();
 
         // Read back the content to verify
-        let mut reader = File::open(&file_path).unwrap();
-        let mut gz_reader = GzDecoder::new(&mut reader);
-        let mut decompressed_content = Vec::new();
-        gz_reader.read_to_end(&mut decompressed_content).unwrap();
+        let decompressed_content = block_file.read_at(0, initial_content.len() as u64).unwrap();
 
         // Construct expected content
         let mut expected_content = initial_content.to_vec();
         let hash_bytes = hash_to_write.as_bytes();
-        expected_content[6..6+hash_bytes.len()].copy_from_slice(hash_bytes);
+        expected_content[6..6 + hash_bytes.len()].copy_from_slice(hash_bytes);
 
         // Assert that the content matches
         assert_eq!(expected_content, decompressed_content);
     }
+
+    #[test]
+    fn test_write_hash_file_gz_extends_past_current_length() {
+        let file_path = create_temp_file("test_hash_file_extend.blk");
+        let block_file = BlockCompressedFile::create(file_path.clone()).unwrap();
+        block_file.append(b"short").unwrap();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&file_path)
+            .unwrap();
+
+        write_string_file_gz("tail".to_string(), &mut file, 10).unwrap();
+
+        let content = block_file.read_at(0, 14).unwrap();
+        let mut expected = b"short".to_vec();
+        expected.resize(10, 0);
+        expected.extend_from_slice(b"tail");
+        assert_eq!(expected, content);
+    }
+
+    #[test]
+    fn test_write_empty_hash_to_brand_new_block_file() {
+        let file_path = create_temp_file("test_hash_file_empty_new.blk");
+        BlockCompressedFile::create(file_path.clone()).unwrap();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&file_path)
+            .unwrap();
+
+        // An empty hash at position 0 of a freshly created (empty) block
+        // file must not panic: there are no existing blocks and none get
+        // added, so there is nothing to patch.
+        write_string_file_gz(String::new(), &mut file, 0).unwrap();
+
+        let block_file = BlockCompressedFile::new(file_path);
+        assert_eq!(block_file.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_block_file_rejects_index_with_bogus_entry_count() {
+        let file_path = create_temp_file("test_block_corrupt_index.blk");
+        let block_file = BlockCompressedFile::create(file_path.clone()).unwrap();
+        block_file.append(b"some data").unwrap();
+
+        // Overwrite the index's leading entry-count field with a huge value
+        // while leaving the index's actual byte length (the trailer read by
+        // `read_index`) unchanged, simulating a truncated/corrupt index.
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&file_path)
+            .unwrap();
+        let file_len = file.metadata().unwrap().len();
+        file.seek(SeekFrom::Start(file_len - 8)).unwrap();
+        let mut index_len_bytes = [0u8; 8];
+        file.read_exact(&mut index_len_bytes).unwrap();
+        let index_len = u64::from_le_bytes(index_len_bytes);
+        let index_start = file_len - 8 - index_len;
+        file.seek(SeekFrom::Start(index_start)).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+
+        // Reading back must surface a graceful error instead of aborting on
+        // an oversized allocation.
+        let err = block_file.len().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_block_compressed_file_read_at_and_append() {
+        let path = create_temp_file("test_block_file.blk");
+        let block_file = BlockCompressedFile::create(path).unwrap();
+
+        let first_half = vec![b'a'; block::BLOCK_SIZE + 10];
+        block_file.append(&first_half).unwrap();
+        block_file.append(b"more-data").unwrap();
+
+        assert_eq!(block_file.len().unwrap(), (block::BLOCK_SIZE + 19) as u64);
+
+        // Read a window spanning the boundary between the block holding the
+        // tail of the first append and the block holding the second append.
+        let window = block_file
+            .read_at(block::BLOCK_SIZE as u64 + 5, 10)
+            .unwrap();
+        let mut expected = vec![b'a'; 5];
+        expected.extend_from_slice(b"more-");
+        assert_eq!(expected, window);
+    }
+
+    #[test]
+    fn test_open_auto_detects_gzip() {
+        let path = create_temp_file("test_open_auto.gz");
+        let content = b"auto-detected gzip content";
+
+        let file = File::create(&path).unwrap();
+        let mut gz_writer = GzEncoder::new(file, Compression::default());
+        gz_writer.write_all(content).unwrap();
+        gz_writer.finish().unwrap();
+
+        let mut reader = CompressedFile::open_auto(path).unwrap();
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+
+        assert_eq!(content, &decoded[..]);
+    }
+
+    #[test]
+    fn test_open_auto_falls_back_to_passthrough_for_plain_text() {
+        let path = create_temp_file("test_open_auto_plain.txt");
+        let content = b"just plain, uncompressed bytes";
+        fs::write(&path, content).unwrap();
+
+        let mut reader = CompressedFile::open_auto(path).unwrap();
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+
+        assert_eq!(content, &decoded[..]);
+    }
+
+    #[test]
+    fn test_open_auto_detects_zstd_and_bzip2() {
+        for method in [CompressionMethod::Zstd, CompressionMethod::Bzip2] {
+            let path = create_temp_file(&format!("test_open_auto_{method:?}.bin"));
+            let content = b"auto-detected by magic bytes";
+
+            let file = File::create(&path).unwrap();
+            let options = CompressionOptions { method, level: 6 };
+            let mut writer = options.build_encoder(file);
+            writer.write_all(content).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = CompressedFile::open_auto(path).unwrap();
+            let mut decoded = Vec::new();
+            reader.read_to_end(&mut decoded).unwrap();
+
+            assert_eq!(content, &decoded[..], "method {method:?} auto-detect failed");
+        }
+    }
+
+    #[test]
+    fn test_encrypted_write_and_read_roundtrip() {
+        let path = create_temp_file("test_encrypted_roundtrip.gz.enc");
+        let passphrase = "correct horse battery staple";
+        let content = b"Secrets, compressed then encrypted.";
+
+        let compressed_file = CompressedFile::create_encrypted(path.clone()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write_encrypted(passphrase).unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        let mut reader = compressed_file.open_for_read_encrypted(passphrase).unwrap();
+        let mut decrypted_content = Vec::new();
+        reader.read_to_end(&mut decrypted_content).unwrap();
+
+        assert_eq!(content, &decrypted_content[..]);
+    }
+
+    #[test]
+    fn test_encrypted_write_twice_reuses_no_salt_or_iv() {
+        let path = create_temp_file("test_encrypted_rewrite.gz.enc");
+        let passphrase = "correct horse battery staple";
+
+        let compressed_file = CompressedFile::create_encrypted(path.clone()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write_encrypted(passphrase).unwrap();
+            writer.write_all(b"a much longer first message").unwrap();
+        }
+        let first_header = fs::read(&path).unwrap()[..crypto::HEADER_LEN].to_vec();
+
+        let second_content = b"short";
+        {
+            let mut writer = compressed_file.open_for_write_encrypted(passphrase).unwrap();
+            writer.write_all(second_content).unwrap();
+        }
+        let second_header = fs::read(&path).unwrap()[..crypto::HEADER_LEN].to_vec();
+
+        // Each open_for_write_encrypted call must mint a fresh salt/IV rather
+        // than reusing the keystream from the previous write.
+        assert_ne!(first_header, second_header);
+
+        // Re-opening for a shorter write must truncate away the first
+        // write's trailing bytes rather than leaving stale ciphertext past
+        // the new EOF.
+        let mut reader = compressed_file.open_for_read_encrypted(passphrase).unwrap();
+        let mut decrypted_content = Vec::new();
+        reader.read_to_end(&mut decrypted_content).unwrap();
+        assert_eq!(second_content, &decrypted_content[..]);
+    }
+
+    #[test]
+    fn test_encrypted_read_wrong_passphrase_fails() {
+        let path = create_temp_file("test_encrypted_wrong_passphrase.gz.enc");
+        let content = b"Secrets, compressed then encrypted.";
+
+        let compressed_file = CompressedFile::create_encrypted(path.clone()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write_encrypted("right-pass").unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        let result = compressed_file
+            .open_for_read_encrypted("wrong-pass")
+            .and_then(|mut reader| {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)
+            });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_encrypted_read_detects_tampering() {
+        let path = create_temp_file("test_encrypted_tampered.gz.enc");
+        let passphrase = "correct horse battery staple";
+        let content = b"Secrets, compressed then encrypted.";
+
+        let compressed_file = CompressedFile::create_encrypted(path.clone()).unwrap();
+        {
+            let mut writer = compressed_file.open_for_write_encrypted(passphrase).unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        // Flip the last byte of the ciphertext+tag region.
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.seek(SeekFrom::End(-1)).unwrap();
+        let mut last_byte = [0u8; 1];
+        file.read_exact(&mut last_byte).unwrap();
+        file.seek(SeekFrom::Start(len - 1)).unwrap();
+        file.write_all(&[last_byte[0] ^ 0xFF]).unwrap();
+
+        let result = compressed_file
+            .open_for_read_encrypted(passphrase)
+            .and_then(|mut reader| {
+                let mut buf = Vec::new();
+                reader.read_to_end(&mut buf)
+            });
+
+        assert!(result.is_err());
+    }
 }